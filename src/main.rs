@@ -1,28 +1,46 @@
 use rspotify::prelude::*;
 use rspotify::{scopes, AuthCodeSpotify, Credentials, OAuth, ClientResult, ClientError, Token};
-use rspotify::model::{SimplifiedPlaylist, PlaylistItem, PlaylistId, PlayableItem, Market, FullEpisode, FullTrack};
-use rspotify::clients::pagination::Paginator;
-use std::{io, fmt, thread, time};
+use rspotify::http::HttpError;
+use rspotify::model::{
+    SimplifiedPlaylist, PlaylistItem, PlaylistId, PlayableItem, PlayableId, TrackId, Market,
+    FullEpisode, FullTrack
+};
+use futures::stream::{self, StreamExt};
+use tokio::net::TcpListener;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::{io, fmt};
+use std::time::Duration;
 use std::error::Error;
+use std::future::Future;
+use std::collections::HashSet;
+use std::cmp::Ordering;
+
+//Default wait when Spotify 429s us without a Retry-After header, and the starting point for the
+//exponential backoff used when that keeps happening.
+const DEFAULT_RETRY_AFTER_SECS: u64 = 5;
+//Upper bound for the backoff, so a run of bad luck doesn't have us sleeping for an hour.
+const MAX_BACKOFF_SECS: u64 = 60;
+//Spotify's own page size ceiling, used whenever we paginate manually.
+const PAGE_LIMIT: u32 = 50;
+//Spotify's own cap on how many URIs a single playlist_add_items call accepts.
+const ADD_ITEMS_LIMIT: usize = 100;
+//How many pages we let run concurrently at once. Spotify's rate limit, not our own throughput,
+//is the actual bottleneck, so there's no point throwing every page at it at the same time.
+const CONCURRENT_PAGE_FETCHES: usize = 5;
+//Host and port of the loopback redirect URI registered for this app. Has to match whatever's set
+//as RSPOTIFY_REDIRECT_URI (e.g. http://127.0.0.1:8888/callback), since Spotify only ever redirects
+//back to the exact URI on file.
+const CALLBACK_SERVER_ADDR: &str = "127.0.0.1:8888";
 
 //This is an error enum, so i can have my personalized errors and to be able to put all of them in
 //the function annotation inside the result.
 #[derive(Debug)]
 enum OrderingError {
     SpotifyError(ClientError),
-    EpisodeInPlaylist(FullEpisode),
-    LocalMusicInPlaylist(FullTrack),
-    EmptyArgOnMusic(String, String),
 }
 impl fmt::Display for OrderingError {
     fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::EpisodeInPlaylist(episode) => write!(formatter, "The playlist cannot be sorted, \
-            as it has a podcast: {}", episode.name),
-            Self::LocalMusicInPlaylist(music) => write!(formatter, "The playlist cannot be sorted, \
-            as it has a music from your storage: {}", music.name),
-            Self::EmptyArgOnMusic(param, music) => write!(formatter, "The sorting process failed \
-            because the music {} param {} is blank", music, param),
             Self::SpotifyError(original_error) => write!(formatter, "{}", original_error),
         }
     }
@@ -34,6 +52,48 @@ impl From<ClientError> for OrderingError {
 }
 impl Error for OrderingError {}
 
+//Checks whether a ClientError is a plain HTTP 429, and if so, what Retry-After (in seconds) came
+//back with it. The outer Option is None for anything that isn't a rate limit, meaning the caller
+//should give up instead of retrying; the inner Option is None when Spotify didn't send a header.
+fn retry_after_secs(error: &ClientError) -> Option<Option<u64>> {
+    match error {
+        ClientError::Http(http_error) => match http_error.as_ref() {
+            HttpError::StatusCode(response) if response.status() == 429 => {
+                Some(response.headers().get("Retry-After").and_then(|value| value.parse::<u64>().ok()))
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+//Runs a Spotify call, and if it comes back as a 429, sleeps (honoring Retry-After when Spotify
+//sends one) and tries again instead of bubbling up the error. When Spotify doesn't tell us how
+//long to wait, we fall back to an exponentially growing backoff (capped at MAX_BACKOFF_SECS) so
+//repeated failures don't turn into a hot retry loop. Every other error is handed back unchanged.
+async fn with_retry<T, F, Fut>(mut op: F) -> Result<T, OrderingError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = ClientResult<T>>,
+{
+    let mut backoff_secs = DEFAULT_RETRY_AFTER_SECS;
+    loop {
+        match op().await {
+            Ok(result) => return Ok(result),
+            Err(error) => {
+                let wait_secs = match retry_after_secs(&error) {
+                    Some(Some(secs)) => secs,
+                    Some(None) => backoff_secs,
+                    None => return Err(OrderingError::SpotifyError(error)),
+                };
+                println!("Spotify rate limited us, waiting {}s before retrying...", wait_secs);
+                tokio::time::sleep(Duration::from_secs(wait_secs)).await;
+                backoff_secs = (backoff_secs * 2).min(MAX_BACKOFF_SECS);
+            },
+        }
+    }
+}
+
 #[derive(Debug)]
 struct CachedLoginError;
 impl fmt::Display for CachedLoginError {
@@ -46,107 +106,396 @@ impl Error for CachedLoginError {}
 //This function will get a cached token from the storage. If it fails reading or the token does not
 //exist, it'll return an error, indicating that the program should ask for the authorization. If its
 //successfull, it'll return only a ok, since the spotify object will aready be with the token refreshed
-fn read_cached_token(spotify: &mut AuthCodeSpotify) -> Result<(), CachedLoginError>{
+async fn read_cached_token(spotify: &mut AuthCodeSpotify) -> Result<(), CachedLoginError>{
     let token = match Token::from_cache(".token_cache"){
         Ok(result) => Some(result),
         Err(_) => return Err(CachedLoginError),
     };
-    match spotify.token.lock(){
-        Ok(mut token_ref) => *token_ref = token,
-        Err(_) => return Err(CachedLoginError),
-    }
-    match spotify.refresh_token(){
+    *spotify.token.lock().await = token;
+    match spotify.refresh_token().await{
         Ok(_) => {},
         Err(_) => return Err(CachedLoginError),
     }
     Ok(())
 }
 
+//Pulls the `code` query param out of a callback request's path, e.g. "/callback?code=abc&state=xyz".
+//None if there's no query string at all or no `code` param in it.
+fn extract_code_param(path: &str) -> Option<String> {
+    let query = path.split('?').nth(1)?;
+    query.split('&').find_map(|pair| pair.strip_prefix("code=")).map(String::from)
+}
+
+//Tries to finish the login without making the user copy-paste the redirected URL: opens the
+//authorize URL in the browser, listens once on CALLBACK_SERVER_ADDR for Spotify's redirect,
+//grabs the `code` off of it, and exchanges that for a token. Whatever goes wrong along the way
+//(can't bind the port, can't open a browser, no code in the callback...) is handed back as an
+//error so the caller can fall back to the manual prompt instead.
+async fn authenticate_via_callback_server(
+    spotify: &mut AuthCodeSpotify,
+    authorize_url: &str
+) -> Result<(), Box<dyn Error>> {
+    let listener = TcpListener::bind(CALLBACK_SERVER_ADDR).await?;
+    open::that(authorize_url)?;
+
+    let (mut stream, _) = listener.accept().await?;
+    let mut buffer = [0u8; 2048];
+    let bytes_read = stream.read(&mut buffer).await?;
+    let request = String::from_utf8_lossy(&buffer[..bytes_read]);
+    let request_path = request.lines()
+        .next()
+        .and_then(|request_line| request_line.split_whitespace().nth(1))
+        .ok_or("Spotify's callback request didn't look like HTTP")?;
+    let code = extract_code_param(request_path).ok_or("Callback request had no ?code= in it")?;
+
+    let response_body = "Logged in, you can close this tab and go back to the terminal.";
+    stream.write_all(format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response_body.len(), response_body
+    ).as_bytes()).await?;
+
+    spotify.request_token(&code).await?;
+    Ok(())
+}
+
 //This function get a list of all of the user's playlists, filtered by only the ones they own.
-//It can error when the lib errors by some reason (since the iterator return the playlists inside
-//a result that must be checked)
-fn get_user_playlists(spotify: &AuthCodeSpotify) -> Result<Vec<SimplifiedPlaylist>, OrderingError> {
-    let current_user_id = String::from(spotify.current_user()?.id.id());
+//It can error when the lib errors by some reason. The first page tells us the total, so every
+//other page can be fetched concurrently (capped at CONCURRENT_PAGE_FETCHES at a time) instead of
+//waiting on one round trip per page.
+async fn get_user_playlists(spotify: &AuthCodeSpotify) -> Result<Vec<SimplifiedPlaylist>, OrderingError> {
+    let current_user_id = String::from(with_retry(|| spotify.current_user()).await?.id.id());
+
+    let first_page = with_retry(|| spotify.current_user_playlists_manual(Some(PAGE_LIMIT), Some(0))).await?;
+    let total = first_page.total;
+    let mut pages: Vec<(u32, Vec<SimplifiedPlaylist>)> = vec![(0, first_page.items)];
+
+    let remaining_offsets: Vec<u32> = (PAGE_LIMIT..total).step_by(PAGE_LIMIT as usize).collect();
+    let fetched_pages: Vec<Result<(u32, Vec<SimplifiedPlaylist>), OrderingError>> = stream::iter(remaining_offsets)
+        .map(|offset| async move {
+            with_retry(|| spotify.current_user_playlists_manual(Some(PAGE_LIMIT), Some(offset)))
+                .await
+                .map(|page| (offset, page.items))
+        })
+        .buffered(CONCURRENT_PAGE_FETCHES)
+        .collect()
+        .await;
+    for page in fetched_pages {
+        pages.push(page?);
+    }
+    pages.sort_by_key(|(offset, _)| *offset);
+
     let mut user_owned_playlists: Vec<SimplifiedPlaylist> = Vec::new();
-    for playlist in spotify.current_user_playlists(){
-        let playlist = playlist?;
-        if playlist.owner.id.id() == current_user_id {
-            user_owned_playlists.push(playlist);
+    for (_, items) in pages {
+        for playlist in items {
+            if playlist.owner.id.id() == current_user_id {
+                user_owned_playlists.push(playlist);
+            }
         }
     }
     Ok(user_owned_playlists)
 }
 
-//This function get two vectors, one having all the strings in the original order of the playlist
-//and one with them ordered. To be honest, I dont know if I should make it only return an unordered
-//vector and them order it outside the function... Or return the ordered list as a slice, since
-//it won't be muted in the program.... But now it is what it is.
-//It can error when the playlist has a podcast or a local music, and also when a music have a blank
-//parameter.
-//TODO: Implement ordering customization?
-fn get_music_list(
-    playlist_iterable: Paginator<ClientResult<PlaylistItem>>
-) -> Result<(Vec<String>, Vec<String>), OrderingError> {
-    let mut unordered_music_list: Vec<String> = Vec::new();
-    for music in playlist_iterable{
-        let music = match music?.track{
-            Some(track) => match track{
-                PlayableItem::Track(music) => music,
-                PlayableItem::Episode(podcast) => return Err(OrderingError::EpisodeInPlaylist(podcast))},
-            None => panic!("Since I don't know why a playlist item may not have anything associated \
-                            with it, I'll leave this without further handling."),
+//Fetches every item of a playlist via manual pagination, concurrently once the first page tells
+//us the total, and hands them back in real playlist order. Shared by get_music_list and
+//playlist_track_ids so neither has to duplicate the fetch-then-reassemble dance.
+async fn fetch_playlist_items(
+    spotify: &AuthCodeSpotify,
+    playlist_id: PlaylistId
+) -> Result<Vec<PlaylistItem>, OrderingError> {
+    let first_page = with_retry(|| spotify.playlist_items_manual(
+        playlist_id.clone(), None, Some(Market::FromToken), Some(PAGE_LIMIT), Some(0)
+    )).await?;
+    let total = first_page.total;
+    let mut pages: Vec<(u32, Vec<PlaylistItem>)> = vec![(0, first_page.items)];
+
+    let remaining_offsets: Vec<u32> = (PAGE_LIMIT..total).step_by(PAGE_LIMIT as usize).collect();
+    let fetched_pages: Vec<Result<(u32, Vec<PlaylistItem>), OrderingError>> = stream::iter(remaining_offsets)
+        .map(|offset| {
+            let playlist_id = playlist_id.clone();
+            async move {
+                with_retry(|| spotify.playlist_items_manual(
+                    playlist_id.clone(), None, Some(Market::FromToken), Some(PAGE_LIMIT), Some(offset)
+                ))
+                    .await
+                    .map(|page| (offset, page.items))
+            }
+        })
+        .buffered(CONCURRENT_PAGE_FETCHES)
+        .collect()
+        .await;
+    for page in fetched_pages {
+        pages.push(page?);
+    }
+    pages.sort_by_key(|(offset, _)| *offset);
+
+    Ok(pages.into_iter().flat_map(|(_, items)| items).collect())
+}
+
+//A playlist item that isn't necessarily a sortable track: podcasts and local files don't have the
+//metadata (or, for episodes, the type) our sort keys compare on, but playlist_reorder_items moves
+//them by position just the same, so they still need a place in the index space reorder_musics works over.
+#[derive(Debug, Clone)]
+enum Playable {
+    Track(FullTrack),
+    Episode(FullEpisode),
+}
+impl Playable {
+    fn name(&self) -> &str {
+        match self {
+            Self::Track(track) => &track.name,
+            Self::Episode(episode) => &episode.name,
+        }
+    }
+}
+
+//One playlist item, paired with its TrackMeta when it's something we know how to compare (a
+//non-local track) and None otherwise (a podcast, or a local file). See sort_playlist_entries for
+//what happens to the None ones.
+#[derive(Debug, Clone)]
+struct PlaylistEntry {
+    playable: Playable,
+    meta: Option<TrackMeta>,
+}
+
+//Structured stand-in for the old single `artist-release_date-album-disc-track-name` label, so the
+//user can choose how tracks get compared instead of the ordering being baked into the string. The
+//release date fields stay as Option: a track missing them just has nothing to compare on that key
+//(see compare_optional), rather than the whole sort aborting like the old EmptyArgOnMusic did.
+#[derive(Debug, Clone)]
+struct TrackMeta {
+    artist: String,
+    release_date: Option<String>,
+    album: String,
+    disc_number: i32,
+    track_number: i32,
+    name: String,
+    added_at: Option<String>,
+    duration_ms: i64,
+}
+
+//Which field of a TrackMeta a SortSpec compares on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortKey {
+    Artist,
+    ReleaseDate,
+    Album,
+    DiscTrack,
+    Name,
+    DateAdded,
+    Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortDirection {
+    Ascending,
+    Descending,
+}
+
+//One entry of the user's chosen sort order, e.g. "album ascending, then track number ascending".
+#[derive(Debug, Clone, Copy)]
+struct SortSpec {
+    key: SortKey,
+    direction: SortDirection,
+}
+
+//The sort the tool used before this became configurable, kept as the default when the user just
+//presses enter: artist, then release date, then album, then disc/track number, then name.
+fn default_sort_specs() -> Vec<SortSpec> {
+    vec![
+        SortSpec { key: SortKey::Artist, direction: SortDirection::Ascending },
+        SortSpec { key: SortKey::ReleaseDate, direction: SortDirection::Ascending },
+        SortSpec { key: SortKey::Album, direction: SortDirection::Ascending },
+        SortSpec { key: SortKey::DiscTrack, direction: SortDirection::Ascending },
+        SortSpec { key: SortKey::Name, direction: SortDirection::Ascending },
+    ]
+}
+
+fn parse_sort_key(token: &str) -> Option<SortKey> {
+    match token.to_lowercase().as_str() {
+        "artist" => Some(SortKey::Artist),
+        "release_date" | "release-date" | "date" => Some(SortKey::ReleaseDate),
+        "album" => Some(SortKey::Album),
+        "disc_track" | "disc-track" | "track" => Some(SortKey::DiscTrack),
+        "name" => Some(SortKey::Name),
+        "date_added" | "added" => Some(SortKey::DateAdded),
+        "duration" => Some(SortKey::Duration),
+        _ => None,
+    }
+}
+
+//Reads a space-separated list of sort keys from the user (optionally prefixed with `-` for
+//descending), e.g. "album -date_added" meaning "album ascending, then date added descending".
+//An empty line, or a line where every token failed to parse, falls back to default_sort_specs.
+fn prompt_sort_specs() -> Vec<SortSpec> {
+    println!(
+        "Type the sort keys in priority order (artist, release_date, album, disc_track, name, \
+        date_added, duration), prefixing a key with - to sort it descending. \
+        Leave blank for the default (artist release_date album disc_track name)."
+    );
+    let mut input = String::new();
+    io::stdin().read_line(&mut input).expect("Something bad happend while reading input.");
+
+    let mut specs: Vec<SortSpec> = Vec::new();
+    for token in input.split_whitespace() {
+        let (direction, key_token) = match token.strip_prefix('-') {
+            Some(rest) => (SortDirection::Descending, rest),
+            None => (SortDirection::Ascending, token),
         };
-        if music.is_local{
-            return Err(OrderingError::LocalMusicInPlaylist(music));
+        match parse_sort_key(key_token) {
+            Some(key) => specs.push(SortSpec { key, direction }),
+            None => println!("Ignoring unknown sort key \"{}\".", key_token),
         }
-        let mut music_label = String::new();
-        music_label.push_str(&music.artists[0].name);
-        music_label.push('-');
-        music_label.push_str(
-            &music.album.release_date.ok_or(
-                OrderingError::EmptyArgOnMusic(String::from("album.release_date"), music.name.clone())
-            )?
-        );
-        let release_date_precision = music.album.release_date_precision.ok_or(
-            OrderingError::EmptyArgOnMusic(String::from("album.release_date_precision"), music.name.clone())
-        )?;
-        if release_date_precision == "year"{
-            music_label.push_str("-01-01");
-        } else if release_date_precision == "month"{
-            music_label.push_str("-01");
+    }
+    if specs.is_empty() {
+        default_sort_specs()
+    } else {
+        specs
+    }
+}
+
+//Missing fields sort after present ones regardless of direction, rather than erroring like the
+//old string-label sort did on a blank release_date/release_date_precision. compare_by_key hands
+//this result back as is for the optional keys, skipping its own direction reversal, so the
+//direction is taken as an argument and applied here only to the Some/Some case; that way a
+//missing field still sorts last instead of first when sorting Descending.
+fn compare_optional<T: Ord>(a: &Option<T>, b: &Option<T>, direction: SortDirection) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => match direction {
+            SortDirection::Ascending => a.cmp(b),
+            SortDirection::Descending => b.cmp(a),
+        },
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+fn compare_by_key(a: &TrackMeta, b: &TrackMeta, spec: &SortSpec) -> Ordering {
+    match spec.key {
+        SortKey::ReleaseDate => return compare_optional(&a.release_date, &b.release_date, spec.direction),
+        SortKey::DateAdded => return compare_optional(&a.added_at, &b.added_at, spec.direction),
+        _ => {},
+    }
+    let ordering = match spec.key {
+        SortKey::Artist => a.artist.cmp(&b.artist),
+        SortKey::Album => a.album.cmp(&b.album),
+        SortKey::DiscTrack => (a.disc_number, a.track_number).cmp(&(b.disc_number, b.track_number)),
+        SortKey::Name => a.name.cmp(&b.name),
+        SortKey::Duration => a.duration_ms.cmp(&b.duration_ms),
+        SortKey::ReleaseDate | SortKey::DateAdded => unreachable!("handled above"),
+    };
+    match spec.direction {
+        SortDirection::Ascending => ordering,
+        SortDirection::Descending => ordering.reverse(),
+    }
+}
+
+//Sorts the entries that have a TrackMeta by comparing the given keys in priority order, and
+//groups everything else (podcasts, local files) at the end in their original relative order,
+//rather than aborting the whole sort the way the old EpisodeInPlaylist/LocalMusicInPlaylist
+//errors did. Returns the original indices in their new order, so the caller can tell where each
+//entry came from. Ties among sortable entries are broken on the original index, so entries with
+//identical keys keep their existing relative position instead of getting shuffled by an unstable sort.
+fn sort_playlist_entries(entries: &[PlaylistEntry], sort_specs: &[SortSpec]) -> Vec<usize> {
+    let (mut sortable, pinned): (Vec<usize>, Vec<usize>) = (0..entries.len())
+        .partition(|&index| entries[index].meta.is_some());
+    sortable.sort_by(|&a, &b| {
+        let meta_a = entries[a].meta.as_ref().unwrap();
+        let meta_b = entries[b].meta.as_ref().unwrap();
+        for spec in sort_specs {
+            let ordering = compare_by_key(meta_a, meta_b, spec);
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
         }
-        music_label.push('-');
-        music_label.push_str(&music.album.name);
-        music_label.push('-');
-        music_label.push_str(&format!("{:0>6}", music.disc_number));
-        music_label.push('-');
-        music_label.push_str(&format!("{:0>6}", music.track_number));
-        music_label.push('-');
-        music_label.push_str(&music.name);
-        unordered_music_list.push(music_label);
-    }
-
-    let mut ordered_music_list = unordered_music_list.clone().to_vec();
-    ordered_music_list.sort_unstable();
-    Ok((unordered_music_list, ordered_music_list))
+        a.cmp(&b)
+    });
+    sortable.into_iter().chain(pinned).collect()
+}
+
+//Collects the stable track IDs of a playlist, for comparing playlists by track identity instead
+//of by display label. Episodes and local tracks have no track ID, so they're just skipped here
+//rather than kept as meta-less entries like get_music_list does, since dedup has no use for
+//pinning them in place.
+async fn playlist_track_ids(
+    spotify: &AuthCodeSpotify,
+    playlist_id: PlaylistId
+) -> Result<Vec<TrackId<'static>>, OrderingError> {
+    let items = fetch_playlist_items(spotify, playlist_id).await?;
+    Ok(items.into_iter()
+        .filter_map(|item| match item.track {
+            Some(PlayableItem::Track(track)) => track.id,
+            _ => None,
+        })
+        .collect())
+}
+
+//This function gets a PlaylistEntry per item in the playlist, in the playlist's own order. The
+//caller decides how to sort them (see sort_playlist_entries) instead of this baking in one fixed
+//order. Podcasts and local files no longer abort the whole thing: they just come back with
+//meta: None, since there's nothing on them our sort keys can compare.
+async fn get_music_list(
+    spotify: &AuthCodeSpotify,
+    playlist_id: PlaylistId
+) -> Result<Vec<PlaylistEntry>, OrderingError> {
+    let mut entries: Vec<PlaylistEntry> = Vec::new();
+    for item in fetch_playlist_items(spotify, playlist_id).await?{
+        let added_at = item.added_at.map(|added_at| added_at.to_rfc3339());
+        let playable = match item.track{
+            Some(PlayableItem::Track(music)) => Playable::Track(music),
+            Some(PlayableItem::Episode(podcast)) => Playable::Episode(podcast),
+            None => panic!("Since I don't know why a playlist item may not have anything associated \
+                            with it, I'll leave this without further handling."),
+        };
+        let meta = match &playable {
+            Playable::Track(music) if !music.is_local => {
+                let release_date = match (music.album.release_date.clone(), music.album.release_date_precision.as_deref()) {
+                    (Some(date), Some("year")) => Some(format!("{}-01-01", date)),
+                    (Some(date), Some("month")) => Some(format!("{}-01", date)),
+                    (Some(date), _) => Some(date),
+                    (None, _) => None,
+                };
+                Some(TrackMeta {
+                    artist: music.artists[0].name.clone(),
+                    release_date,
+                    album: music.album.name.clone(),
+                    disc_number: music.disc_number,
+                    track_number: music.track_number,
+                    name: music.name.clone(),
+                    added_at,
+                    duration_ms: music.duration.num_milliseconds(),
+                })
+            },
+            //A podcast, or a local file: nothing here to build a TrackMeta's fields from, so it's
+            //left out of the sort and grouped at the end instead (see sort_playlist_entries).
+            _ => None,
+        };
+        entries.push(PlaylistEntry { playable, meta });
+    }
+    Ok(entries)
 }
 
 //This function makes all the operations on the spotify side, and only returns a empty tuple if success,
 //or an error saying why it failed (errors in the lib/api size). It consumes both vectors.
-fn reorder_musics(
+//Unlike the fetching above, this stays sequential: each reorder shifts the positions every
+//following move depends on, so they can't be parallelized.
+//Works on the entries' original indices rather than the old string labels, since
+//sort_playlist_entries already gives us a unique, stable identity for every entry (labels could,
+//in principle, collide). entries is only used to print what's currently being moved.
+async fn reorder_musics(
     spotify: &AuthCodeSpotify,
     playlist_id: PlaylistId,
-    ordered_music_list: Vec<String>,
-    unordered_music_list: &mut Vec<String>
+    entries: &[PlaylistEntry],
+    ordered_music_list: Vec<usize>,
+    unordered_music_list: &mut Vec<usize>
 ) -> Result<(), OrderingError> {
     let mut ordered_list_index: usize = 0;
     let mut unordered_list_index: usize;
     let mut music_sequence_count: usize = 0;
     let music_len: usize = ordered_music_list.len();
-    let delay_time: usize = ordered_music_list.len()*2;
 
     while ordered_list_index < music_len{
-        let current_music: &str = &ordered_music_list[ordered_list_index];
+        let current_music: usize = ordered_music_list[ordered_list_index];
         unordered_list_index = unordered_music_list.iter()
                                                     .position(|x| x == &current_music)
                                                     .unwrap(); //This should never error!
@@ -155,41 +504,129 @@ fn reorder_musics(
             continue;
         }
         if ordered_list_index+1 != music_len && unordered_list_index+1 != music_len{
-            while &ordered_music_list[ordered_list_index + 1 + music_sequence_count] ==
-                  &unordered_music_list[unordered_list_index + 1 + music_sequence_count] {
+            while ordered_music_list[ordered_list_index + 1 + music_sequence_count] ==
+                  unordered_music_list[unordered_list_index + 1 + music_sequence_count] {
                 music_sequence_count += 1;
                 if ordered_list_index + music_sequence_count + 1 == music_len ||
                 unordered_list_index + music_sequence_count + 1 == music_len {break;}
             }
         }
-        println!("Currently working on {}", current_music);
-        spotify.playlist_reorder_items(
+        println!("Currently working on {}", entries[current_music].playable.name());
+        with_retry(|| spotify.playlist_reorder_items(
             playlist_id.clone(),
             Some(unordered_list_index as i32),
             Some(ordered_list_index as i32),
             Some(music_sequence_count as u32 + 1),
             None
-        )?;
+        )).await?;
         //Original idea was moving each music individually, removing and adding again, till I found
         //about the drain method, which can help when moving multiple musics. I don't know if this can be
         //a bad move for moving only one element, and I need to further research about that.
         let moved_musics = unordered_music_list.drain(
             unordered_list_index..unordered_list_index+music_sequence_count+1
-        ).collect::<Vec<String>>();
+        ).collect::<Vec<usize>>();
         for (position, item) in moved_musics.iter().enumerate(){
-            unordered_music_list.insert(ordered_list_index + position, item.to_string());
+            unordered_music_list.insert(ordered_list_index + position, *item);
         }
         ordered_list_index += 1;
         music_sequence_count = 0;
-        thread::sleep(time::Duration::from_millis(delay_time as u64)); //Idk if I should let this here,
-        //in the past Spotify sometimes ignored the changes I made if I didn't used this delay, but now
-        //it seems stable, so that's another thing I need to research about.
+        //Used to sleep a fixed, guessed amount of time here so Spotify wouldn't drop the change.
+        //Now that with_retry waits on Spotify's own Retry-After instead of a guess, that's gone.
     }
     Ok(())
 }
 
+//Companion mode to sorting: lets the user pick two or more of their playlists and reports which
+//tracks are shared across all of them (by track ID, not display name) versus unique to each, with
+//the option of spinning the shared tracks off into a brand new playlist.
+async fn run_comparison_flow(spotify: &AuthCodeSpotify, user_owned_playlists: &[SimplifiedPlaylist]) {
+    println!("Type the numbers of two or more playlists to compare, separated by spaces.");
+    let selected_playlists: Vec<&SimplifiedPlaylist> = loop {
+        let mut selection_string = String::new();
+        io::stdin().read_line(&mut selection_string).expect("Something bad happend while reading input.");
+        let indexes: Option<Vec<usize>> = selection_string.split_whitespace()
+            .map(|token| token.parse::<usize>().ok())
+            .collect();
+        let indexes = match indexes {
+            Some(indexes) if indexes.len() >= 2 => indexes,
+            _ => {
+                println!("Please type at least two valid numbers, separated by spaces.");
+                continue;
+            },
+        };
+        if indexes.iter().any(|&index| index < 1 || index > user_owned_playlists.len()){
+            println!("One of the numbers you wrote doesn't ressemble any playlist in the list.");
+            continue;
+        }
+        break indexes.iter().map(|&index| &user_owned_playlists[index - 1]).collect();
+    };
+
+    let mut track_ids_by_playlist: Vec<(&SimplifiedPlaylist, HashSet<TrackId<'static>>)> = Vec::new();
+    for playlist in &selected_playlists {
+        let ids = match playlist_track_ids(spotify, playlist.id.clone()).await{
+            Ok(ids) => ids,
+            Err(error) => panic!("{}", error),
+        };
+        track_ids_by_playlist.push((*playlist, ids.into_iter().collect()));
+    }
+
+    let mut intersection = track_ids_by_playlist[0].1.clone();
+    for (_, ids) in &track_ids_by_playlist[1..] {
+        intersection = intersection.intersection(ids).cloned().collect();
+    }
+
+    println!("\n{} tracks are shared across all {} selected playlists:", intersection.len(), selected_playlists.len());
+    for id in &intersection {
+        println!("  {}", id.id());
+    }
+    //A track is unique to a playlist only if none of the *other* selected playlists also have it;
+    //ids.difference(&intersection) would also count tracks shared by some-but-not-all playlists,
+    //which isn't what "unique" means once there are 3+ playlists in the comparison.
+    for (index, (playlist, ids)) in track_ids_by_playlist.iter().enumerate() {
+        let in_others: HashSet<TrackId> = track_ids_by_playlist.iter().enumerate()
+            .filter(|&(other_index, _)| other_index != index)
+            .flat_map(|(_, (_, other_ids))| other_ids.iter().cloned())
+            .collect();
+        let unique_count = ids.difference(&in_others).count();
+        println!("{} has {} tracks not present in any other selected playlist.", playlist.name, unique_count);
+    }
+    if intersection.is_empty() {
+        return;
+    }
+
+    println!("\nCreate a new playlist with just the shared tracks? Type yes to proceed.");
+    let mut user_confirmation = String::new();
+    io::stdin().read_line(&mut user_confirmation).unwrap();
+    if user_confirmation.trim() != "yes"{
+        println!("Operation cancelled.");
+        return;
+    }
+
+    let current_user_id = match with_retry(|| spotify.current_user()).await{
+        Ok(user) => user.id,
+        Err(error) => panic!("{}", error),
+    };
+    let new_playlist = match with_retry(|| spotify.user_playlist_create(
+        current_user_id.clone(), "Shared tracks", Some(false), None, None
+    )).await{
+        Ok(playlist) => playlist,
+        Err(error) => panic!("{}", error),
+    };
+    //playlist_add_items caps out at ADD_ITEMS_LIMIT URIs per call and rspotify doesn't chunk for
+    //us, so a shared set bigger than that has to go in over several requests.
+    let items: Vec<PlayableId> = intersection.iter().map(|id| PlayableId::Track(id.clone())).collect();
+    for batch in items.chunks(ADD_ITEMS_LIMIT) {
+        match with_retry(|| spotify.playlist_add_items(new_playlist.id.clone(), batch.iter().cloned(), None)).await{
+            Ok(_) => {},
+            Err(error) => panic!("{}", error),
+        }
+    }
+    println!("Created playlist \"{}\" with the {} shared tracks.", new_playlist.name, intersection.len());
+}
+
 
-fn main(){
+#[tokio::main]
+async fn main(){
     //Code to authenticate in spotify.
     //I've commited some war crimes in this part a.k.a nested matchs but now I really don't know
     //how to make it better.
@@ -198,27 +635,33 @@ fn main(){
         scopes!("playlist-read-private", "playlist-modify-private", "playlist-modify-public")
     ).unwrap();
     let mut spotify = AuthCodeSpotify::new(credentials, oauth);
-    match read_cached_token(&mut spotify){
+    match read_cached_token(&mut spotify).await{
         Ok(_) => {}, //Already logged, do nothing
         Err(_) => { //No cached login, asks for authentication
             let url = spotify.get_authorize_url(false).expect("Unknown error.");
-            match spotify.prompt_for_token(&url){
-                Ok(_) => match spotify.token.lock(){ //Logged in,just trying to cache token
-                    //I unwrap the as_ref here since the spotify object shouldn't be without a token...
-                    Ok(token_ref) => match token_ref.as_ref().unwrap().write_cache(".token_cache"){
-                        Ok(_) => println!("Successfully cached token."),
-                        Err(_) => println!("Couldn't cache the token"),
-                    },
-                    Err(_) => println!("Couldn't cache the token"),
+            match authenticate_via_callback_server(&mut spotify, &url).await{
+                Ok(_) => {}, //Logged in through the loopback callback, nothing else to do.
+                Err(error) => { //No browser/loopback available, falls back to the copy-paste prompt.
+                    println!("Couldn't complete the automatic login ({}), falling back to pasting \
+                    the redirected URL by hand.", error);
+                    match spotify.prompt_for_token(&url).await{
+                        Ok(_) => {},
+                        Err(error) => panic!("Failed to authenticate into spotify: {}", error),
+                    }
                 },
-                Err(error) => panic!("Failed to authenticate into spotify: {}", error),
+            }
+            //I unwrap the as_ref here since the spotify object shouldn't be without a token...
+            let token_ref = spotify.token.lock().await;
+            match token_ref.as_ref().unwrap().write_cache(".token_cache"){
+                Ok(_) => println!("Successfully cached token."),
+                Err(_) => println!("Couldn't cache the token"),
             }
         },
     }
-    println!("I've sucefully logged in as {}.", spotify.current_user().unwrap().display_name.unwrap());
+    println!("I've sucefully logged in as {}.", with_retry(|| spotify.current_user()).await.unwrap().display_name.unwrap());
 
     //Code to get and print playlists, also handle whe a user own no playlist.
-    let user_owned_playlists = match get_user_playlists(&spotify){
+    let user_owned_playlists = match get_user_playlists(&spotify).await{
         Ok(vector) => vector,
         Err(error) => panic!("{}", error),
     };
@@ -230,6 +673,28 @@ fn main(){
         println!("{} - {}", number + 1, playlist.name);
     }
 
+    //Asks what the user actually wants to do with those playlists.
+    let mut mode_string: String;
+    let mut mode: usize;
+    loop{
+        println!("\nWhat do you want to do?\n1 - Sort a playlist\n2 - Compare playlists for duplicates");
+        mode_string = String::new();
+        io::stdin().read_line(&mut mode_string).expect("Something bad happend while reading input.");
+        mode = match mode_string.trim().parse::<usize>() {
+            Ok(1) => 1,
+            Ok(2) => 2,
+            _ => {
+                println!("Please type 1 or 2.");
+                continue;
+            },
+        };
+        break;
+    }
+    if mode == 2 {
+        run_comparison_flow(&spotify, &user_owned_playlists).await;
+        return;
+    }
+
     //This part about which playlist should be reordered.
     let mut pl_index_string: String;
     let mut pl_index: usize;
@@ -256,16 +721,16 @@ fn main(){
 
     //Now, the code get the playlist items.
     let playlist_id = user_owned_playlists[pl_index - 1].id.clone();
-    let playlist = spotify.playlist_items(
-        playlist_id.clone(),
-        None, //I first tryed to use a scopes string, but that got me only headache and a bad sleep night.
-        Some(Market::FromToken)
-    );
-    let (mut unordered_music_list, ordered_music_list) = match get_music_list(playlist){
+    let entries = match get_music_list(&spotify, playlist_id.clone()).await{
         Ok(results) => results,
         Err(error) => panic!("{}", error),
     };
 
+    //Asks which keys to sort by, then builds the ordered/unordered index lists reorder_musics needs.
+    let sort_specs = prompt_sort_specs();
+    let ordered_music_list = sort_playlist_entries(&entries, &sort_specs);
+    let mut unordered_music_list: Vec<usize> = (0..entries.len()).collect();
+
     //Asks confirmation and then does the black magic.
     println!(
         "You've chosen playlist {} (with ID {}) containing {} musics. \
@@ -283,9 +748,10 @@ fn main(){
         match reorder_musics(
             &spotify,
             playlist_id,
+            &entries,
             ordered_music_list,
             &mut unordered_music_list
-        ){
+        ).await{
             Ok(_) => println!("Sucessfull operation. The playlist is now sorted"),
             Err(error) => println!("{}", error),
         }